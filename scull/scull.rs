@@ -3,15 +3,21 @@
 //! The Scull device from LDD3, reimplemented in Rust.
 
 use kernel::{
+    bindings,
     c_str,
+    fs::poll::{PollCondVar, PollTable},
     fs::{file, File, Kiocb},
-    ioctl::{_IOC_NR, _IOC_SIZE, _IOC_TYPE, _IOR},
+    ioctl::{_IO, _IOC_NR, _IOC_SIZE, _IOC_TYPE, _IOR, _IOW, _IOWR},
     iov::{IovIterDest, IovIterSource},
     kvec,
     miscdevice::{MiscDevice, MiscDeviceOptions, MiscDeviceRegistration},
     new_mutex,
+    new_poll_condvar,
     prelude::*,
-    sync::Mutex,
+    proc_fs::ProcDirEntry,
+    seq_file::{SeqFile, SeqOperations},
+    str::CStr,
+    sync::{Arc, Mutex},
     types::ForeignOwnable,
     uaccess::UserSlice,
 };
@@ -22,10 +28,55 @@ module! {
     authors: ["Emmanuel Amoah"],
     description: "The Scull device from LDD3, reimplemented in Rust.",
     license: "GPL",
+    params: {
+        scull_nr_devs: u32 {
+            default: 4,
+            permissions: 0o444,
+            description: "Number of scull devices (scull0..scullN-1) to register",
+        },
+        scull_quantum: u32 {
+            default: 4000,
+            permissions: 0o444,
+            description: "Default quantum size for each scull device",
+        },
+        scull_qset: u32 {
+            default: 1000,
+            permissions: 0o444,
+            description: "Default qset size for each scull device",
+        },
+        scull_access_mode: u32 {
+            default: 0,
+            permissions: 0o444,
+            description: "Access policy: 0=unrestricted (default), 1=single-open (scullsingle), 2=uid-restricted (sculluid)",
+        },
+    },
 }
 
-const SCULL_QUANTUM: u32 = 4000;
-const SCULL_QSET: u32 = 1000;
+const SCULL_ACCESS_OPEN: u32 = 0;
+const SCULL_ACCESS_SINGLE: u32 = 1;
+const SCULL_ACCESS_UID: u32 = 2;
+
+// Cached copy of the `scull_access_mode` parameter, read once at module
+// init: `open()`/`release()` are associated functions with no route back
+// to `ThisModule`, so the value is snapshotted here instead of re-reading
+// the param on every open.
+static SCULL_ACCESS_MODE: core::sync::atomic::AtomicU32 =
+    core::sync::atomic::AtomicU32::new(SCULL_ACCESS_OPEN);
+
+// `scull_nr_devs` picks how many of these get registered; keeping the
+// name table static sidesteps needing a heap-allocated, 'static `CStr`
+// per device.
+const SCULL_MAX_DEVICES: usize = 8;
+const SCULL_DEVICE_NAMES: [&CStr; SCULL_MAX_DEVICES] = [
+    c_str!("scull0"),
+    c_str!("scull1"),
+    c_str!("scull2"),
+    c_str!("scull3"),
+    c_str!("scull4"),
+    c_str!("scull5"),
+    c_str!("scull6"),
+    c_str!("scull7"),
+];
 
 // Ioctl definitions
 
@@ -33,25 +84,139 @@ const SCULL_QSET: u32 = 1000;
 const SCULL_IOC_MAGIC: u32 = '`' as u32;
 // Please use a different 8-bit number in your code
 
+const SCULL_IOCSQUANTUM: u32 = _IOW::<i32>(SCULL_IOC_MAGIC, 1);
+const SCULL_IOCSQSET: u32 = _IOW::<i32>(SCULL_IOC_MAGIC, 2);
+const SCULL_IOCTQUANTUM: u32 = _IO(SCULL_IOC_MAGIC, 3);
+const SCULL_IOCTQSET: u32 = _IO(SCULL_IOC_MAGIC, 4);
 const SCULL_IOCGQUANTUM: u32 = _IOR::<i32>(SCULL_IOC_MAGIC, 5);
 const SCULL_IOCGQSET: u32 = _IOR::<i32>(SCULL_IOC_MAGIC, 6);
+const SCULL_IOCQQUANTUM: u32 = _IO(SCULL_IOC_MAGIC, 7);
+const SCULL_IOCQQSET: u32 = _IO(SCULL_IOC_MAGIC, 8);
+const SCULL_IOCXQUANTUM: u32 = _IOWR::<i32>(SCULL_IOC_MAGIC, 9);
+const SCULL_IOCXQSET: u32 = _IOWR::<i32>(SCULL_IOC_MAGIC, 10);
+const SCULL_IOCHQUANTUM: u32 = _IO(SCULL_IOC_MAGIC, 11);
+const SCULL_IOCHQSET: u32 = _IO(SCULL_IOC_MAGIC, 12);
+
+const SCULL_IOC_MAXNR: u32 = 12;
+
+// Returns whether the current task may reconfigure device-wide tunables.
+// The safe `Credential` wrapper doesn't expose capability checks yet, so
+// fall through to the raw helper, as LDD3's `capable(CAP_SYS_ADMIN)` does.
+fn has_cap_sys_admin() -> bool {
+    // SAFETY: `capable` merely inspects the current task's credentials and
+    // has no safety requirements of its own.
+    unsafe { bindings::capable(bindings::CAP_SYS_ADMIN as i32) }
+}
 
-const SCULL_IOC_MAXNR: u32 = 6;
+// Returns the effective uid of the current task, for `sculluid`-style
+// ownership checks. Like `has_cap_sys_admin`, this reaches past the safe
+// `Credential` wrapper straight to the raw helper.
+fn current_euid() -> u32 {
+    // SAFETY: `current_euid` just inspects the current task's credentials.
+    unsafe { bindings::current_euid() }.val
+}
 
 #[pin_data]
 struct ScullDeviceModule {
+    // One misc device per `scull_nr_devs`, named "scull0".."scullN-1"; each
+    // entry is independently pinned since `MiscDeviceRegistration` is
+    // address-sensitive, but the `KVec` holding them is free to move.
+    _miscdev_regs: KVec<Pin<KBox<ScullDeviceRegistration>>>,
+    #[pin]
+    _pipe_reg: ScullPipeRegistration,
+    // Read-only /proc/scullmem dump; built from a snapshot of the device
+    // list above, since that list is fixed once the module has loaded.
     #[pin]
-    _miscdev_reg: MiscDeviceRegistration<ScullDevice>,
+    _proc_entry: ProcDirEntry<ScullProcSeq>,
 }
 
 impl kernel::InPlaceModule for ScullDeviceModule {
-    fn init(_module: &'static ThisModule) -> impl PinInit<Self, Error> {
-        let options = MiscDeviceOptions {
-            name: c_str!("scull"),
-        };
-
+    fn init(module: &'static ThisModule) -> impl PinInit<Self, Error> {
         try_pin_init!(ScullDeviceModule {
-            _miscdev_reg <- MiscDeviceRegistration::register(options),
+            _miscdev_regs: {
+                let lock = module.kernel_param_lock();
+                let nr_devs = *scull_nr_devs.read(&lock) as usize;
+                let quantum = *scull_quantum.read(&lock);
+                let qset = *scull_qset.read(&lock);
+                let access_mode = *scull_access_mode.read(&lock);
+                drop(lock);
+
+                if nr_devs == 0 || nr_devs > SCULL_MAX_DEVICES {
+                    return Err(EINVAL);
+                }
+                if quantum == 0 || qset == 0 {
+                    return Err(EINVAL);
+                }
+                if !matches!(
+                    access_mode,
+                    SCULL_ACCESS_OPEN | SCULL_ACCESS_SINGLE | SCULL_ACCESS_UID
+                ) {
+                    return Err(EINVAL);
+                }
+                SCULL_ACCESS_MODE.store(access_mode, core::sync::atomic::Ordering::Relaxed);
+
+                let mut regs = KVec::with_capacity(nr_devs, GFP_KERNEL)?;
+                for name in &SCULL_DEVICE_NAMES[..nr_devs] {
+                    regs.push(
+                        KBox::try_pin_init(
+                            ScullDeviceRegistration::new(name, quantum, qset),
+                            GFP_KERNEL,
+                        )?,
+                        GFP_KERNEL,
+                    )?;
+                }
+                regs
+            },
+            _pipe_reg <- ScullPipeRegistration::new(c_str!("scullpipe")),
+            _proc_entry <- {
+                let mut devices = KVec::with_capacity(_miscdev_regs.len(), GFP_KERNEL)?;
+                for reg in _miscdev_regs.iter() {
+                    devices.push((reg.name, reg.state.clone()), GFP_KERNEL)?;
+                }
+
+                ProcDirEntry::register_seq_file::<ScullProcSeq>(
+                    c_str!("scullmem"),
+                    Arc::new(ScullProcDevices(devices), GFP_KERNEL)?,
+                )
+            },
+        })
+    }
+}
+
+// Owns the quantum-set storage for one scull minor for as long as the
+// module stays loaded, and the `MiscDeviceRegistration` through which opens
+// of that minor arrive. `ScullDeviceHandle::open` recovers this container
+// from the registration via `container_of!` and hands out a clone of the
+// shared `state` handle, so data written through one `open()` is visible
+// to the next instead of being dropped on `release`.
+#[pin_data]
+struct ScullDeviceRegistration {
+    name: &'static CStr,
+    state: Arc<Mutex<ScullDevice>>,
+    #[pin]
+    misc: MiscDeviceRegistration<ScullDeviceHandle>,
+}
+
+impl ScullDeviceRegistration {
+    // `quantum`/`qset` seed the device's tunables from the `scull_quantum`/
+    // `scull_qset` module params; later changed per-device via the
+    // SET/TELL/EXCHANGE/SHIFT ioctls.
+    fn new(name: &'static CStr, quantum: u32, qset: u32) -> impl PinInit<Self, Error> {
+        try_pin_init!(Self {
+            name,
+            state: Arc::pin_init(
+                new_mutex!(ScullDevice {
+                    data: None,
+                    qset,
+                    size: 0,
+                    quantum,
+                    free_quanta: KVec::new(),
+                    open_count: 0,
+                    owner_uid: None,
+                }),
+                GFP_KERNEL,
+            )?,
+            misc <- MiscDeviceRegistration::register(MiscDeviceOptions { name }),
         })
     }
 }
@@ -70,20 +235,56 @@ impl ScullQset {
     }
 }
 
+// Quantum buffers released by `trim()` are kept on each device's
+// `free_quanta` list, scullc-style, instead of being freed outright, so a write/trim cycle that keeps reusing the
+// same `quantum` size doesn't keep re-allocating under `GFP_KERNEL`. Capped
+// so a single trim can't pin down unbounded memory, and flushed whenever
+// `quantum` changes since a stale-sized buffer can't be reused.
+const SCULL_FREE_LIST_CAP: usize = 16;
+
 struct ScullDevice {
     data: Option<KBox<ScullQset>>,
     qset: u32,
     size: usize,
     quantum: u32,
+    free_quanta: KVec<KBox<KVec<u8>>>,
+    // Access-control bookkeeping for `scull_access_mode`; unused (stays 0 /
+    // `None`) in the default unrestricted mode.
+    open_count: u32,
+    owner_uid: Option<u32>,
 }
 
 impl ScullDevice {
+    // Change `quantum`, flushing the free list since its buffers are sized
+    // for the old quantum and can't be recycled against the new one.
+    fn set_quantum(&mut self, quantum: u32) {
+        self.quantum = quantum;
+        self.free_quanta.clear();
+    }
+
     // Empty out the scull device; must be called with the device
-    // mutex locked.
+    // mutex locked. Quantum buffers are pushed onto the free list (up to
+    // the cap) rather than dropped, so the next write/trim cycle can reuse
+    // them. Buffers whose length no longer matches `quantum` (from before a
+    // quantum-changing ioctl) are dropped instead of recycled, since
+    // `write_iter` trusts a popped buffer to already be `quantum`-sized.
     fn trim(&mut self) {
-        if let Some(data) = self.data.take() {
-            drop(data);
-        };
+        let mut node = self.data.take();
+        while let Some(mut qs) = node {
+            if let Some(data) = qs.data.take() {
+                for quantum_buf in data {
+                    let Some(buf) = quantum_buf else {
+                        continue;
+                    };
+                    if buf.len() == self.quantum as usize
+                        && self.free_quanta.len() < SCULL_FREE_LIST_CAP
+                    {
+                        let _ = self.free_quanta.push(buf, GFP_KERNEL);
+                    }
+                }
+            }
+            node = qs.next.take();
+        }
         self.size = 0;
     }
 
@@ -111,31 +312,70 @@ impl ScullDevice {
     }
 }
 
+// Marker type carrying the `MiscDevice` vtable for a scull minor; the
+// actual per-minor state lives in the `ScullDeviceRegistration` that owns
+// this handle's `MiscDeviceRegistration`, see the comment there.
+struct ScullDeviceHandle;
+
 #[vtable]
-impl MiscDevice for ScullDevice {
-    type Ptr = Pin<KBox<Mutex<Self>>>;
-
-    fn open(file: &File, _misc: &MiscDeviceRegistration<Self>) -> Result<Self::Ptr> {
-        let dev = KBox::pin_init(
-            new_mutex!(ScullDevice {
-                data: None,
-                qset: SCULL_QSET,
-                size: 0,
-                quantum: SCULL_QUANTUM,
-            }),
-            GFP_KERNEL,
-        )?;
-
-        // Now trim to 0 the length of the device if open was write-only
-        // (currently redundant)
+impl MiscDevice for ScullDeviceHandle {
+    type Ptr = Arc<Mutex<ScullDevice>>;
+
+    fn open(file: &File, misc: &MiscDeviceRegistration<Self>) -> Result<Self::Ptr> {
+        // SAFETY: every `MiscDeviceRegistration<ScullDeviceHandle>` this
+        // driver creates is the `misc` field of a `ScullDeviceRegistration`
+        // that was pinned in place for the module's lifetime before this
+        // registration's fops could be reached, so `misc` is always
+        // preceded in memory by the rest of its container.
+        let reg = unsafe { &*kernel::container_of!(misc, ScullDeviceRegistration, misc) };
+        let dev = reg.state.clone();
+
+        let mut guard = dev.lock();
+        match SCULL_ACCESS_MODE.load(core::sync::atomic::Ordering::Relaxed) {
+            SCULL_ACCESS_SINGLE => {
+                // scullsingle: only one open at a time, unless the caller
+                // can override via CAP_SYS_ADMIN.
+                if guard.open_count > 0 && !has_cap_sys_admin() {
+                    return Err(EBUSY);
+                }
+                guard.open_count += 1;
+            }
+            SCULL_ACCESS_UID => {
+                // sculluid: first opener claims the device; later opens by
+                // a different uid are rejected unless privileged.
+                let euid = current_euid();
+                if guard.open_count == 0 {
+                    guard.owner_uid = Some(euid);
+                } else if guard.owner_uid != Some(euid) && !has_cap_sys_admin() {
+                    return Err(EBUSY);
+                }
+                guard.open_count += 1;
+            }
+            _ => {}
+        }
+
+        // Trim to 0 the length of the device if open was write-only, same
+        // as LDD3's scull_open.
         if (file.flags() & file::flags::O_ACCMODE) == file::flags::O_WRONLY {
-            let mut dev = dev.lock();
-            dev.trim();
+            guard.trim();
         }
+        drop(guard);
 
         Ok(dev)
     }
 
+    fn release(dev: <Self::Ptr as ForeignOwnable>::Borrowed<'_>, _file: &File) {
+        // Only single-open/uid mode track `open_count`; in the default
+        // mode it stays 0 and this is a no-op.
+        if SCULL_ACCESS_MODE.load(core::sync::atomic::Ordering::Relaxed) != SCULL_ACCESS_OPEN {
+            let mut guard = dev.lock();
+            guard.open_count = guard.open_count.saturating_sub(1);
+            if guard.open_count == 0 {
+                guard.owner_uid = None;
+            }
+        }
+    }
+
     // Data management: read and write
 
     fn read_iter(mut kiocb: Kiocb<'_, Self::Ptr>, iov: &mut IovIterDest<'_>) -> Result<usize> {
@@ -203,6 +443,10 @@ impl MiscDevice for ScullDevice {
         s_pos = rest / quantum;
         q_pos = rest % quantum;
 
+        // Pop a free-list candidate before taking `dev.follow()`'s borrow;
+        // pushed back below if it turns out not to be needed.
+        let mut spare_quantum = dev.free_quanta.pop();
+
         // Follow the list up to the right position
         dptr = dev.follow(item);
 
@@ -220,7 +464,13 @@ impl MiscDevice for ScullDevice {
         let data = data.as_mut().ok_or(retval)?;
 
         if data[s_pos].is_none() {
-            data[s_pos] = Some(KBox::new(kvec![0; quantum]?, GFP_KERNEL)?);
+            data[s_pos] = Some(match spare_quantum.take() {
+                Some(mut buf) => {
+                    buf.fill(0);
+                    buf
+                }
+                None => KBox::new(kvec![0; quantum]?, GFP_KERNEL)?,
+            });
         }
 
         let quantum_vec = data[s_pos].as_mut().ok_or(retval)?;
@@ -238,6 +488,13 @@ impl MiscDevice for ScullDevice {
             dev.size = kiocb.ki_pos() as usize
         }
 
+        // Wasn't consumed above: hand it back to the free list.
+        if let Some(buf) = spare_quantum {
+            if dev.free_quanta.len() < SCULL_FREE_LIST_CAP {
+                let _ = dev.free_quanta.push(buf, GFP_KERNEL);
+            }
+        }
+
         Ok(retval)
     }
 
@@ -249,7 +506,7 @@ impl MiscDevice for ScullDevice {
         cmd: u32,
         arg: usize,
     ) -> Result<isize> {
-        let arg = UserPtr::from_addr(arg);
+        let user_ptr = UserPtr::from_addr(arg);
         let size = _IOC_SIZE(cmd);
 
         // Extract the type and number bitfields, and don't decode
@@ -261,15 +518,94 @@ impl MiscDevice for ScullDevice {
             return Err(ENOTTY);
         }
 
-        let mut writer = UserSlice::new(arg, size).writer();
+        // SET/TELL/EXCHANGE/SHIFT all mutate device-wide tunables, so only
+        // a privileged caller may issue them; GET/QUERY stay unrestricted.
+        let requires_admin = matches!(
+            cmd,
+            SCULL_IOCSQUANTUM
+                | SCULL_IOCSQSET
+                | SCULL_IOCTQUANTUM
+                | SCULL_IOCTQSET
+                | SCULL_IOCXQUANTUM
+                | SCULL_IOCXQSET
+                | SCULL_IOCHQUANTUM
+                | SCULL_IOCHQSET
+        );
+        if requires_admin && !has_cap_sys_admin() {
+            return Err(EPERM);
+        }
 
-        let dev = dev.lock();
+        let mut dev = dev.lock();
         match cmd {
+            SCULL_IOCSQUANTUM => {
+                let new_quantum = UserSlice::new(user_ptr, size).reader().read::<i32>()?;
+                if new_quantum == 0 {
+                    return Err(EINVAL);
+                }
+                dev.set_quantum(new_quantum as u32);
+            }
+            SCULL_IOCSQSET => {
+                let new_qset = UserSlice::new(user_ptr, size).reader().read::<i32>()?;
+                if new_qset == 0 {
+                    return Err(EINVAL);
+                }
+                dev.qset = new_qset as u32;
+            }
+            SCULL_IOCTQUANTUM => {
+                if arg == 0 {
+                    return Err(EINVAL);
+                }
+                dev.set_quantum(arg as u32);
+            }
+            SCULL_IOCTQSET => {
+                if arg == 0 {
+                    return Err(EINVAL);
+                }
+                dev.qset = arg as u32;
+            }
             SCULL_IOCGQUANTUM => {
-                writer.write(&dev.quantum)?;
+                UserSlice::new(user_ptr, size).writer().write(&dev.quantum)?;
             }
             SCULL_IOCGQSET => {
-                writer.write(&dev.qset)?;
+                UserSlice::new(user_ptr, size).writer().write(&dev.qset)?;
+            }
+            SCULL_IOCQQUANTUM => return Ok(dev.quantum as isize),
+            SCULL_IOCQQSET => return Ok(dev.qset as isize),
+            SCULL_IOCXQUANTUM => {
+                let new_quantum = UserSlice::new(user_ptr, size).reader().read::<i32>()?;
+                if new_quantum == 0 {
+                    return Err(EINVAL);
+                }
+                let old_quantum = dev.quantum;
+                UserSlice::new(user_ptr, size)
+                    .writer()
+                    .write(&old_quantum)?;
+                dev.set_quantum(new_quantum as u32);
+            }
+            SCULL_IOCXQSET => {
+                let new_qset = UserSlice::new(user_ptr, size).reader().read::<i32>()?;
+                if new_qset == 0 {
+                    return Err(EINVAL);
+                }
+                let old_qset = dev.qset;
+                UserSlice::new(user_ptr, size).writer().write(&old_qset)?;
+                dev.qset = new_qset as u32;
+            }
+            SCULL_IOCHQUANTUM => {
+                if arg == 0 {
+                    return Err(EINVAL);
+                }
+                let old_quantum = dev.quantum;
+                dev.set_quantum(arg as u32);
+                return Ok(old_quantum as isize);
+            }
+            SCULL_IOCHQSET => {
+                if arg == 0 {
+                    return Err(EINVAL);
+                }
+                let old_qset = dev.qset;
+                dev.qset = arg as u32;
+                return Ok(old_qset as isize);
             }
             _ => return Err(ENOTTY),
         }
@@ -277,3 +613,271 @@ impl MiscDevice for ScullDevice {
         Ok(0)
     }
 }
+
+// The scullpipe device: a blocking producer/consumer FIFO, modeled on
+// LDD3's scullpipe rather than scull's random-access quantum sets.
+
+const SCULL_P_BUFFER: usize = 4000;
+
+struct ScullPipeInner {
+    buffer: KVec<u8>,
+    buffer_size: usize,
+    // Index of the next byte to be read.
+    rp: usize,
+    // Index of the next byte to be written.
+    wp: usize,
+    nreaders: u32,
+    nwriters: u32,
+}
+
+impl ScullPipeInner {
+    // How many bytes are available to read.
+    fn datasize(&self) -> usize {
+        (self.wp + self.buffer_size - self.rp) % self.buffer_size
+    }
+
+    // How many bytes may still be written before the buffer is full; kept
+    // one slot shy of `buffer_size` so `rp == wp` unambiguously means empty.
+    fn spacefree(&self) -> usize {
+        self.buffer_size - 1 - self.datasize()
+    }
+}
+
+// The buffer, read/write offsets and wait queues shared by every opener of
+// scullpipe. Owned by `ScullPipeRegistration` for the module's lifetime, so
+// a writer's bytes and a reader's wakeup land on the same buffer instead of
+// each `open()` getting its own, as `ScullDeviceRegistration`'s `state` does
+// for the per-minor scull devices.
+#[pin_data]
+struct ScullPipeShared {
+    #[pin]
+    inner: Mutex<ScullPipeInner>,
+    // Woken by writers when data becomes available for a blocked reader.
+    #[pin]
+    inq: PollCondVar,
+    // Woken by readers when space frees up for a blocked writer.
+    #[pin]
+    outq: PollCondVar,
+}
+
+// Owns the scullpipe buffer/wait-queues for as long as the module stays
+// loaded, and the `MiscDeviceRegistration` through which opens arrive.
+// `ScullPipeHandle::open` recovers this container from the registration via
+// `container_of!` and hands out a clone of the shared `state` handle; see
+// `ScullDeviceRegistration` above for the same pattern applied per-minor.
+#[pin_data]
+struct ScullPipeRegistration {
+    state: Arc<ScullPipeShared>,
+    #[pin]
+    misc: MiscDeviceRegistration<ScullPipeHandle>,
+}
+
+impl ScullPipeRegistration {
+    fn new(name: &'static CStr) -> impl PinInit<Self, Error> {
+        try_pin_init!(Self {
+            state: Arc::pin_init(
+                try_pin_init!(ScullPipeShared {
+                    inner <- new_mutex!(ScullPipeInner {
+                        buffer: kvec![0; SCULL_P_BUFFER]?,
+                        buffer_size: SCULL_P_BUFFER,
+                        rp: 0,
+                        wp: 0,
+                        nreaders: 0,
+                        nwriters: 0,
+                    }),
+                    inq <- new_poll_condvar!(),
+                    outq <- new_poll_condvar!(),
+                }),
+                GFP_KERNEL,
+            )?,
+            misc <- MiscDeviceRegistration::register(MiscDeviceOptions { name }),
+        })
+    }
+}
+
+// Marker type carrying the `MiscDevice` vtable for scullpipe; the actual
+// buffer/wait-queue state lives in the `ScullPipeRegistration` that owns
+// this handle's `MiscDeviceRegistration`, see the comment there.
+struct ScullPipeHandle;
+
+#[vtable]
+impl MiscDevice for ScullPipeHandle {
+    type Ptr = Arc<ScullPipeShared>;
+
+    fn open(file: &File, misc: &MiscDeviceRegistration<Self>) -> Result<Self::Ptr> {
+        // SAFETY: every `MiscDeviceRegistration<ScullPipeHandle>` this
+        // driver creates is the `misc` field of a `ScullPipeRegistration`
+        // that was pinned in place for the module's lifetime before this
+        // registration's fops could be reached, so `misc` is always
+        // preceded in memory by the rest of its container.
+        let reg = unsafe { &*kernel::container_of!(misc, ScullPipeRegistration, misc) };
+        let dev = reg.state.clone();
+
+        let accmode = file.flags() & file::flags::O_ACCMODE;
+        let mut guard = dev.inner.lock();
+        if accmode != file::flags::O_WRONLY {
+            guard.nreaders += 1;
+        }
+        if accmode != file::flags::O_RDONLY {
+            guard.nwriters += 1;
+        }
+        drop(guard);
+
+        Ok(dev)
+    }
+
+    fn release(dev: <Self::Ptr as ForeignOwnable>::Borrowed<'_>, file: &File) {
+        let accmode = file.flags() & file::flags::O_ACCMODE;
+        let mut guard = dev.inner.lock();
+        if accmode != file::flags::O_WRONLY {
+            guard.nreaders = guard.nreaders.saturating_sub(1);
+        }
+        if accmode != file::flags::O_RDONLY {
+            guard.nwriters = guard.nwriters.saturating_sub(1);
+        }
+    }
+
+    fn read_iter(mut kiocb: Kiocb<'_, Self::Ptr>, iov: &mut IovIterDest<'_>) -> Result<usize> {
+        let nonblock = (kiocb.ki_filp().flags() & file::flags::O_NONBLOCK) != 0;
+        let dev = kiocb.file();
+        let mut guard = dev.inner.lock();
+
+        while guard.rp == guard.wp {
+            if nonblock {
+                return Err(EAGAIN);
+            }
+            if dev.inq.wait(&mut guard) {
+                return Err(ERESTARTSYS);
+            }
+        }
+
+        let mut count = iov.len();
+        if guard.wp > guard.rp {
+            count = count.min(guard.wp - guard.rp);
+        } else {
+            // Writer has wrapped around; only read up to the end of the
+            // buffer in this pass, the rest follows on the next call.
+            count = count.min(guard.buffer_size - guard.rp);
+        }
+
+        let rp = guard.rp;
+        let retval = iov.copy_to_iter(&guard.buffer[rp..rp + count]);
+        guard.rp = (guard.rp + retval) % guard.buffer_size;
+
+        drop(guard);
+        dev.outq.notify_all();
+
+        Ok(retval)
+    }
+
+    fn write_iter(mut kiocb: Kiocb<'_, Self::Ptr>, iov: &mut IovIterSource<'_>) -> Result<usize> {
+        let nonblock = (kiocb.ki_filp().flags() & file::flags::O_NONBLOCK) != 0;
+        let dev = kiocb.file();
+        let mut guard = dev.inner.lock();
+
+        while guard.spacefree() == 0 {
+            if nonblock {
+                return Err(EAGAIN);
+            }
+            if dev.outq.wait(&mut guard) {
+                return Err(ERESTARTSYS);
+            }
+        }
+
+        let mut count = iov.len().min(guard.spacefree());
+        if guard.wp >= guard.rp {
+            count = count.min(guard.buffer_size - guard.wp);
+        } else {
+            count = count.min(guard.rp - guard.wp - 1);
+        }
+
+        let wp = guard.wp;
+        let retval = iov.copy_from_iter(&mut guard.buffer[wp..wp + count]);
+        guard.wp = (guard.wp + retval) % guard.buffer_size;
+
+        drop(guard);
+        dev.inq.notify_all();
+
+        Ok(retval)
+    }
+
+    fn poll(
+        dev: <Self::Ptr as ForeignOwnable>::Borrowed<'_>,
+        file: &File,
+        table: &PollTable,
+    ) -> Result<u32> {
+        dev.inq.poll_wait(file, table);
+        dev.outq.poll_wait(file, table);
+
+        let guard = dev.inner.lock();
+        let mut mask = 0u32;
+        if guard.rp != guard.wp {
+            mask |= bindings::POLLIN | bindings::POLLRDNORM;
+        }
+        if guard.spacefree() > 0 {
+            mask |= bindings::POLLOUT | bindings::POLLWRNORM;
+        }
+
+        Ok(mask)
+    }
+}
+
+// /proc/scullmem: a read-only seq_file dump of every registered scull
+// device's size/quantum/qset and quantum occupancy, matching LDD3's
+// scull_read_procmem. The device list is a fixed-size snapshot taken at
+// module init, so iteration only needs to lock each device in turn.
+
+struct ScullProcDevices(KVec<(&'static CStr, Arc<Mutex<ScullDevice>>)>);
+
+struct ScullProcSeq;
+
+#[vtable]
+impl SeqOperations for ScullProcSeq {
+    // Index into the device snapshot; `None` once it runs past the end.
+    type Item = usize;
+    type Ptr = Arc<ScullProcDevices>;
+
+    fn start(devices: &ScullProcDevices, pos: usize) -> Option<Self::Item> {
+        (pos < devices.0.len()).then_some(pos)
+    }
+
+    fn next(devices: &ScullProcDevices, item: Self::Item) -> Option<Self::Item> {
+        let next = item + 1;
+        (next < devices.0.len()).then_some(next)
+    }
+
+    fn stop(_devices: &ScullProcDevices, _item: Self::Item) {}
+
+    fn show(devices: &ScullProcDevices, item: &Self::Item, seq: &mut SeqFile) -> Result {
+        let (name, state) = &devices.0[*item];
+        let dev = state.lock();
+
+        kernel::seq_print!(
+            seq,
+            "{}: qset {} quantum {} size {}\n",
+            name.to_str()?,
+            dev.qset,
+            dev.quantum,
+            dev.size
+        );
+
+        let mut listitem = 0usize;
+        let mut qs = dev.data.as_deref();
+        while let Some(node) = qs {
+            if let Some(data) = node.data.as_ref() {
+                let filled = data.iter().filter(|q| q.is_some()).count();
+                kernel::seq_print!(
+                    seq,
+                    "  item {}: {}/{} quanta populated\n",
+                    listitem,
+                    filled,
+                    data.len()
+                );
+            }
+            listitem += 1;
+            qs = node.next.as_deref();
+        }
+
+        Ok(())
+    }
+}